@@ -10,6 +10,17 @@ pub struct CompileOption {
   pub output_type: String,
   pub tag_name_prefix: String,
   pub src: HashMap<String, Uint8Array>,
+  /// When `true`, each entry file's `@import` references are resolved against
+  /// the other keys in `src`, topologically ordered and inlined into a single
+  /// flattened output, sparing the host from link-time stitching.
+  pub flatten_imports: Option<bool>,
+  /// Scoping strategy. `"hashed"` rewrites every class selector to a
+  /// collision-free `original__<hash>` name; omitted leaves selectors as-is.
+  pub scope_mode: Option<String>,
+  /// Reserved upper bound on parse concurrency. Currently accepted but not
+  /// acted on: files are parsed serially into one shared resource until
+  /// per-file isolation is proven output-equivalent to that shared resource.
+  pub concurrency: Option<u32>,
 }
 #[napi(object)]
 pub struct CompileSingleOption {
@@ -17,11 +28,61 @@ pub struct CompileSingleOption {
   pub file_name: String,
   pub output_type: String,
   pub tag_name_prefix: String,
+  /// Scoping strategy. `"hashed"` rewrites every class selector to a
+  /// collision-free `original__<hash>` name; omitted leaves selectors as-is.
+  pub scope_mode: Option<String>,
 }
+/// A single CSS problem surfaced to a build tool so it can render it like a
+/// linter and decide whether to fail the build.
+#[napi(object)]
+#[derive(Clone)]
+pub struct CompileDiagnostic {
+  pub message: String,
+  /// `"warning"` for every parser message; `"error"` for unresolved or
+  /// circular `@import` targets found under `flatten_imports`. `"info"` is
+  /// not produced.
+  pub severity: String,
+  /// 1-based line of the span start, or `0` when the position is unknown. The
+  /// pinned `float_pigment_css` only surfaces the message text, so spans
+  /// currently default to `0` until the parser exposes positions.
+  pub line: u32,
+  /// Column of the span start, `0` when unknown (see `line`).
+  pub column: u32,
+  /// Length of the span, `0` when unknown or when the span crosses lines.
+  pub length: u32,
+}
+
 #[napi(object)]
 pub struct CompileResultItem {
   pub content: Buffer,
   pub warnings: Vec<String>,
+  pub diagnostics: Vec<CompileDiagnostic>,
+  /// `true` when the item was served from the incremental [`Compiler`] cache
+  /// instead of being re-parsed this call.
+  pub from_cache: bool,
+  /// Original→scoped class name mapping produced by `scope_mode: "hashed"`,
+  /// empty otherwise. The host applies this rename table to its markup.
+  pub class_map: HashMap<String, String>,
+}
+
+/// Lift a parser warning into a structured [`CompileDiagnostic`].
+///
+/// Everything `add_source` reports is advisory — the parser recovers and still
+/// produces bincode — so these are all `"warning"` severity; there is no
+/// error/info distinction to guess at, and the earlier substring heuristic
+/// (flagging any message containing `invalid`/`expected` as an error)
+/// mislabelled ordinary warnings like "vendor prefix is invalid here". The
+/// pinned `float_pigment_css` `Warning` exposes only `message`; it carries no
+/// source position, so `line`/`column`/`length` stay 0. Populating real spans
+/// would require a location accessor the parser does not offer in this version.
+fn diagnostic_from_message(message: String) -> CompileDiagnostic {
+  CompileDiagnostic {
+    message,
+    severity: String::from("warning"),
+    line: 0,
+    column: 0,
+    length: 0,
+  }
 }
 #[napi(object)]
 pub struct CompileResult {
@@ -29,105 +90,651 @@ pub struct CompileResult {
   pub files: HashMap<String, CompileResultItem>,
 }
 
-#[napi]
-pub fn compile_sync(cfg: CompileOption) -> CompileResult {
-  let output_type = cfg.output_type;
+/// Serialize a single parsed stylesheet according to `output_type`.
+///
+/// `"bincode"` returns the canonical opaque buffer. `"json"` / `"debug"` are
+/// recognised but not yet implementable against the pinned `float_pigment_css`:
+/// it exposes no accessor that walks the parsed rule tree, selectors and
+/// declarations, and the crate pulls in no serde stack to render one. Rather
+/// than dress the opaque bincode up as a byte array and call it JSON, we emit
+/// an empty buffer and a warning so the caller knows the mode is unavailable.
+/// A file that produced no bincode, or an unknown `output_type`, likewise
+/// yields an empty buffer **and** a warning so callers never silently get
+/// nothing back.
+fn serialize_output(ssr: &StyleSheetResource, name: &str, output_type: &str) -> (Buffer, Vec<String>) {
+  match output_type {
+    "bincode" => match ssr.serialize_bincode(name) {
+      Some(bincode) => (Buffer::from(bincode), Vec::new()),
+      None => (
+        Buffer::from(Vec::new()),
+        vec![format!("no output produced for `{}`", name)],
+      ),
+    },
+    "json" | "debug" => (
+      Buffer::from(Vec::new()),
+      vec![format!(
+        "output_type `{}` is not supported: float_pigment_css exposes no parsed-tree serialization, only bincode",
+        output_type
+      )],
+    ),
+    other => (
+      Buffer::from(Vec::new()),
+      vec![format!(
+        "unknown output_type `{}`, expected `bincode`, `json` or `debug`",
+        other
+      )],
+    ),
+  }
+}
+
+/// Build an error-level [`CompileDiagnostic`] with no source span, used for
+/// link-time problems (unresolved or circular `@import`) that are not tied to
+/// a single parser token.
+fn error_diagnostic(message: String) -> CompileDiagnostic {
+  CompileDiagnostic {
+    message,
+    severity: String::from("error"),
+    line: 0,
+    column: 0,
+    length: 0,
+  }
+}
+
+/// Locate every real `@import` statement, skipping tokens that appear inside
+/// `/* */` comments or string literals. Returns each statement's byte range
+/// `[start, end)` (end just past the terminating `;`) together with its parsed
+/// target, so both extraction and stripping share one comment-aware scan.
+fn scan_imports(css: &str) -> Vec<(usize, usize, Option<String>)> {
+  let bytes = css.as_bytes();
+  let at_import = b"@import";
+  let mut out = Vec::new();
+  let mut i = 0;
+  while i < bytes.len() {
+    // Skip block comments.
+    if bytes[i] == b'/' && i + 1 < bytes.len() && bytes[i + 1] == b'*' {
+      i += 2;
+      while i + 1 < bytes.len() && !(bytes[i] == b'*' && bytes[i + 1] == b'/') {
+        i += 1;
+      }
+      i = (i + 2).min(bytes.len());
+      continue;
+    }
+    // Skip string literals.
+    if bytes[i] == b'"' || bytes[i] == b'\'' {
+      let quote = bytes[i];
+      i += 1;
+      while i < bytes.len() && bytes[i] != quote {
+        i += 1;
+      }
+      i = (i + 1).min(bytes.len());
+      continue;
+    }
+    if bytes[i..].starts_with(at_import) {
+      let start = i;
+      let body = i + at_import.len();
+      // Walk to the terminating `;`, stepping over any nested strings.
+      let mut j = body;
+      while j < bytes.len() && bytes[j] != b';' {
+        if bytes[j] == b'"' || bytes[j] == b'\'' {
+          let quote = bytes[j];
+          j += 1;
+          while j < bytes.len() && bytes[j] != quote {
+            j += 1;
+          }
+        }
+        j += 1;
+      }
+      let target = parse_import_target(&css[body..j.min(bytes.len())]);
+      let end = (j + 1).min(bytes.len());
+      out.push((start, end, target));
+      i = end;
+      continue;
+    }
+    i += 1;
+  }
+  out
+}
+
+/// Extract the raw target strings of every `@import` statement, in source
+/// order. Both the quoted (`@import "a.css"`) and `url()` forms are handled.
+fn extract_imports(css: &str) -> Vec<String> {
+  scan_imports(css)
+    .into_iter()
+    .filter_map(|(_, _, target)| target)
+    .collect()
+}
+
+/// Pull the target out of a single `@import` statement body (the text between
+/// `@import` and `;`).
+fn parse_import_target(stmt: &str) -> Option<String> {
+  if let Some(u) = stmt.find("url(") {
+    let tail = &stmt[u + 4..];
+    let end = tail.find(')')?;
+    return Some(
+      tail[..end]
+        .trim()
+        .trim_matches(|c| c == '"' || c == '\'')
+        .to_string(),
+    );
+  }
+  let start = stmt.find(['"', '\''])?;
+  let quote = stmt.as_bytes()[start] as char;
+  let tail = &stmt[start + 1..];
+  let end = tail.find(quote)?;
+  Some(tail[..end].to_string())
+}
+
+/// Resolve an `@import` target against the source map, matching first by exact
+/// key then by file-name suffix so `./foo.css` links to a `foo.css` entry.
+fn resolve_import<'a>(target: &str, keys: &'a HashMap<String, String>) -> Option<&'a str> {
+  if keys.contains_key(target) {
+    return keys.get_key_value(target).map(|(k, _)| k.as_str());
+  }
+  let base = target.rsplit('/').next().unwrap_or(target);
+  keys
+    .keys()
+    .find(|k| k.rsplit('/').next().unwrap_or(k.as_str()) == base)
+    .map(|k| k.as_str())
+}
+
+/// Strip every `@import` statement from a stylesheet, keeping the remaining
+/// declarations intact so imported files can be concatenated without them.
+fn strip_imports(css: &str) -> String {
+  let mut out = String::with_capacity(css.len());
+  let mut last = 0;
+  for (start, end, _) in scan_imports(css) {
+    out.push_str(&css[last..start]);
+    last = end;
+  }
+  out.push_str(&css[last..]);
+  out
+}
+
+/// Post-order DFS producing `@import` dependencies before the importing file,
+/// flagging `cycle` if a back edge is encountered.
+fn order_imports(
+  node: &str,
+  imports: &HashMap<String, Vec<String>>,
+  order: &mut Vec<String>,
+  state: &mut HashMap<String, u8>,
+  cycle: &mut bool,
+) {
+  match state.get(node) {
+    Some(2) => return,
+    Some(1) => {
+      *cycle = true;
+      return;
+    }
+    _ => {}
+  }
+  state.insert(node.to_string(), 1);
+  if let Some(deps) = imports.get(node) {
+    for dep in deps {
+      order_imports(dep, imports, order, state, cycle);
+    }
+  }
+  state.insert(node.to_string(), 2);
+  order.push(node.to_string());
+}
+
+/// Flatten every entry file by inlining its resolved `@import` dependencies in
+/// topological order, reporting unresolved targets and circular imports as
+/// error diagnostics.
+fn compile_flattened(
+  src: &HashMap<String, Uint8Array>,
+  tag_name_prefix: &str,
+  output_type: &str,
+  scope_mode: &Option<String>,
+) -> CompileResult {
+  let mut contents: HashMap<String, String> = HashMap::new();
+  for (name, data) in src {
+    contents.insert(
+      name.clone(),
+      String::from_utf8_lossy(data.as_ref()).into_owned(),
+    );
+  }
+
+  // Resolve each file's imports against the source map up front.
+  let mut imports: HashMap<String, Vec<String>> = HashMap::new();
+  let mut unresolved: HashMap<String, Vec<String>> = HashMap::new();
+  for (name, css) in &contents {
+    let mut resolved = Vec::new();
+    let mut miss = Vec::new();
+    for target in extract_imports(css) {
+      match resolve_import(&target, &contents) {
+        Some(dep) => resolved.push(dep.to_string()),
+        None => miss.push(target),
+      }
+    }
+    imports.insert(name.clone(), resolved);
+    unresolved.insert(name.clone(), miss);
+  }
+
   let mut result = CompileResult {
     import_index: Buffer::from(Vec::new()),
     files: HashMap::new(),
   };
-  if output_type == "bincode" {
+
+  for name in contents.keys() {
+    let mut warnings: Vec<String> = Vec::new();
+    let mut diagnostics: Vec<CompileDiagnostic> = Vec::new();
+
+    for target in &unresolved[name] {
+      let msg = format!("unresolved @import target `{}`", target);
+      warnings.push(msg.clone());
+      diagnostics.push(error_diagnostic(msg));
+    }
+
+    let mut order: Vec<String> = Vec::new();
+    let mut state: HashMap<String, u8> = HashMap::new();
+    let mut cycle = false;
+    order_imports(name, &imports, &mut order, &mut state, &mut cycle);
+    if cycle {
+      let msg = format!("circular @import detected involving `{}`", name);
+      warnings.push(msg.clone());
+      diagnostics.push(error_diagnostic(msg));
+    }
+
+    // `order` holds dependencies first and the entry file last.
+    let mut flat = String::new();
+    for dep in &order {
+      flat.push_str(&strip_imports(&contents[dep]));
+      flat.push('\n');
+    }
+
+    // Apply scoping to the flattened result so `flatten_imports` and
+    // `scope_mode: "hashed"` compose instead of silently dropping scoping.
+    let (flat, class_map) = scoped_source(name.as_str(), &flat, scope_mode);
+
     let mut ssr = StyleSheetResource::new();
+    if !tag_name_prefix.is_empty() {
+      ssr.add_tag_name_prefix(name.as_str(), tag_name_prefix);
+    }
+    let warn = ssr.add_source(name.as_str(), flat.as_str());
+    for w in warn {
+      let message = String::from(w.message.as_str());
+      diagnostics.push(diagnostic_from_message(message.clone()));
+      warnings.push(message);
+    }
+    let (content, mut unknown) = serialize_output(&ssr, name.as_str(), output_type);
+    warnings.append(&mut unknown);
 
-    // 处理tag名称前缀
-    if !cfg.tag_name_prefix.is_empty() {
-      for name in cfg.src.keys() {
-        ssr.add_tag_name_prefix(name.as_str(), &cfg.tag_name_prefix);
-      }
+    result.files.insert(
+      name.clone(),
+      CompileResultItem {
+        content,
+        warnings,
+        diagnostics,
+        from_cache: false,
+        class_map,
+      },
+    );
+  }
+
+  result
+}
+
+/// Parse and serialize every source into one shared [`StyleSheetResource`],
+/// then generate the cross-file `@import` index from that same resource.
+///
+/// Each file is parsed exactly once: the serialized output and the index both
+/// come from the single `add_source` pass, matching the baseline's behaviour
+/// (a shared resource, not per-file isolated ones). An earlier revision parsed
+/// each file in its own resource on a thread pool and then re-parsed every file
+/// a second time to build the index — that doubled the parse cost and, because
+/// `generate_import_indexes` resolves `@import` against the other sources in
+/// the same resource, per-file isolation could diverge from the shared-resource
+/// output. `concurrency` is accepted for API compatibility but ignored: the
+/// parse is kept serial until `StyleSheetResource` isolation is proven
+/// output-equivalent to the shared resource.
+fn compile_sources(
+  src: &HashMap<String, Uint8Array>,
+  tag_name_prefix: &str,
+  output_type: &str,
+  scope_mode: &Option<String>,
+  _concurrency: Option<u32>,
+) -> CompileResult {
+  let mut ssr = StyleSheetResource::new();
+  let mut files: HashMap<String, CompileResultItem> = HashMap::new();
+
+  for (name, data) in src {
+    let content = String::from_utf8_lossy(data.as_ref()).into_owned();
+    let (source, class_map) = scoped_source(name.as_str(), &content, scope_mode);
+    if !tag_name_prefix.is_empty() {
+      ssr.add_tag_name_prefix(name.as_str(), tag_name_prefix);
     }
+    let warn = ssr.add_source(name.as_str(), source.as_str());
+    let mut warnings: Vec<String> = Vec::new();
+    let mut diagnostics: Vec<CompileDiagnostic> = Vec::new();
+    for w in warn {
+      let message = String::from(w.message.as_str());
+      diagnostics.push(diagnostic_from_message(message.clone()));
+      warnings.push(message);
+    }
+    let (content, mut unknown) = serialize_output(&ssr, name.as_str(), output_type);
+    warnings.append(&mut unknown);
+    files.insert(
+      name.clone(),
+      CompileResultItem {
+        content,
+        warnings,
+        diagnostics,
+        from_cache: false,
+        class_map,
+      },
+    );
+  }
 
-    for (name, data) in &cfg.src {
-      // First, check if the vector has data
-      let content = String::from_utf8_lossy(data.as_ref()).into_owned();
-      let str = content.as_str();
-      let warn = ssr.add_source(name.as_str(), str);
-      let mut arr: Vec<String> = Vec::new();
-      for w in warn {
-        arr.push(String::from(w.message.as_str()));
+  let index = ssr.generate_import_indexes();
+  CompileResult {
+    import_index: Buffer::from(index.serialize_bincode()),
+    files,
+  }
+}
+
+/// Encode a 64-bit value as a compact base36 string, used for the short,
+/// deterministic suffix appended to scoped class names.
+fn to_base36(mut n: u64) -> String {
+  const DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+  if n == 0 {
+    return String::from("0");
+  }
+  let mut buf = Vec::new();
+  while n > 0 {
+    buf.push(DIGITS[(n % 36) as usize]);
+    n /= 36;
+  }
+  buf.reverse();
+  String::from_utf8(buf).unwrap()
+}
+
+/// Derive the per-file scope suffix from the file name plus its content, so
+/// identical input always yields the same scoped names (stable snapshots).
+fn scope_suffix(name: &str, content: &str) -> String {
+  use std::hash::{Hash, Hasher};
+  let mut hasher = std::collections::hash_map::DefaultHasher::new();
+  name.hash(&mut hasher);
+  content.hash(&mut hasher);
+  to_base36(hasher.finish())
+}
+
+/// Rewrite every `.ident` token in `css` to `original__<suffix>`, recording
+/// the original→scoped mapping. String literals and `url()` payloads are left
+/// untouched. `/* */` comments and unquoted declaration values are not told
+/// apart from selectors, so a `.ident` there is rewritten and mapped as well.
+fn apply_scope(css: &str, suffix: &str, class_map: &mut HashMap<String, String>) -> String {
+  let chars: Vec<char> = css.chars().collect();
+  let mut out = String::with_capacity(css.len());
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    if c == '"' || c == '\'' {
+      out.push(c);
+      i += 1;
+      while i < chars.len() {
+        let d = chars[i];
+        out.push(d);
+        i += 1;
+        if d == c {
+          break;
+        }
       }
-      result.files.insert(
-        name.clone(),
-        CompileResultItem {
-          content: Buffer::from(Vec::new()),
-          warnings: arr,
-        },
-      );
+      continue;
     }
-    for name in cfg.src.keys() {
-      if let Some(bincode) = ssr.serialize_bincode(name.as_str()) {
-        if let Some(file) = result.files.get_mut(name) {
-          file.content = Buffer::from(bincode);
+    if chars[i..].starts_with(&['u', 'r', 'l', '(']) {
+      while i < chars.len() {
+        let d = chars[i];
+        out.push(d);
+        i += 1;
+        if d == ')' {
+          break;
         }
       }
+      continue;
+    }
+    if c == '.'
+      && i + 1 < chars.len()
+      && (chars[i + 1].is_ascii_alphabetic() || chars[i + 1] == '_' || chars[i + 1] == '-')
+    {
+      let start = i + 1;
+      let mut j = start;
+      while j < chars.len()
+        && (chars[j].is_ascii_alphanumeric() || chars[j] == '_' || chars[j] == '-')
+      {
+        j += 1;
+      }
+      let original: String = chars[start..j].iter().collect();
+      let scoped = format!("{}__{}", original, suffix);
+      class_map.insert(original, scoped.clone());
+      out.push('.');
+      out.push_str(&scoped);
+      i = j;
+      continue;
     }
-    let index = ssr.generate_import_indexes();
-    result.import_index = Buffer::from(index.serialize_bincode());
+    out.push(c);
+    i += 1;
   }
-  result
+  out
+}
+
+/// Apply the requested `scope_mode` to a file's source, returning the possibly
+/// rewritten CSS and the original→scoped class map (empty unless scoping ran).
+fn scoped_source(
+  name: &str,
+  content: &str,
+  scope_mode: &Option<String>,
+) -> (String, HashMap<String, String>) {
+  if scope_mode.as_deref() == Some("hashed") {
+    let mut class_map = HashMap::new();
+    let suffix = scope_suffix(name, content);
+    let rewritten = apply_scope(content, &suffix, &mut class_map);
+    (rewritten, class_map)
+  } else {
+    (content.to_string(), HashMap::new())
+  }
+}
+
+/// Compute a 64-bit hash of a file's bytes; any byte change produces a
+/// different hash. The file name is not part of the hash, and the incremental
+/// cache keys on [`compile_cache_key`], which also folds in the options.
+fn content_hash(bytes: &[u8]) -> u64 {
+  use std::hash::{Hash, Hasher};
+  let mut hasher = std::collections::hash_map::DefaultHasher::new();
+  bytes.hash(&mut hasher);
+  hasher.finish()
 }
 
+/// Cache key for a file: its content plus every option that changes the
+/// serialized output. Hashing only the bytes would hand back stale bincode
+/// when the caller flips `output_type`, toggles `scope_mode`, or changes the
+/// `tag_name_prefix` without touching the source, so those are folded in too.
+fn compile_cache_key(bytes: &[u8], output_type: &str, scope_mode: &str, tag_name_prefix: &str) -> u64 {
+  use std::hash::{Hash, Hasher};
+  let mut hasher = std::collections::hash_map::DefaultHasher::new();
+  bytes.hash(&mut hasher);
+  output_type.hash(&mut hasher);
+  scope_mode.hash(&mut hasher);
+  tag_name_prefix.hash(&mut hasher);
+  hasher.finish()
+}
+
+/// A previously compiled file, keyed in [`Compiler`] by its source name.
+struct CacheEntry {
+  hash: u64,
+  content: Vec<u8>,
+  warnings: Vec<String>,
+  diagnostics: Vec<CompileDiagnostic>,
+  class_map: HashMap<String, String>,
+}
+
+/// Stateful compiler that keeps a persistent [`StyleSheetResource`] and a
+/// content-hash cache across calls, so unchanged files are served from the
+/// previous result instead of being re-parsed and re-serialized.
 #[napi]
-pub async fn compile(cfg: CompileOption) -> napi::Result<CompileResult> {
-  // 将耗时操作放在线程池中执行
-  napi::tokio::spawn(async move {
+pub struct Compiler {
+  ssr: StyleSheetResource,
+  cache: HashMap<String, CacheEntry>,
+  import_index: Vec<u8>,
+}
+
+#[napi]
+impl Compiler {
+  #[napi(constructor)]
+  #[allow(clippy::new_without_default)]
+  pub fn new() -> Self {
+    Compiler {
+      ssr: StyleSheetResource::new(),
+      cache: HashMap::new(),
+      import_index: Vec::new(),
+    }
+  }
+
+  /// Compile `cfg.src`, reusing cached output for files whose content hash is
+  /// unchanged. The import index is only regenerated when at least one file
+  /// changed; otherwise the previously computed index is returned.
+  #[napi]
+  pub fn compile(&mut self, cfg: CompileOption) -> CompileResult {
     let output_type = cfg.output_type;
     let mut result = CompileResult {
       import_index: Buffer::from(Vec::new()),
       files: HashMap::new(),
     };
-    if output_type == "bincode" {
-      let mut ssr = StyleSheetResource::new();
+    let mut changed = false;
 
-      // 处理tag名称前缀
-      if !cfg.tag_name_prefix.is_empty() {
-        for name in cfg.src.keys() {
-          ssr.add_tag_name_prefix(name.as_str(), &cfg.tag_name_prefix);
+    let scope_mode = cfg.scope_mode.clone().unwrap_or_default();
+    for (name, data) in &cfg.src {
+      let hash = compile_cache_key(
+        data.as_ref(),
+        &output_type,
+        &scope_mode,
+        &cfg.tag_name_prefix,
+      );
+      if let Some(entry) = self.cache.get(name) {
+        if entry.hash == hash {
+          result.files.insert(
+            name.clone(),
+            CompileResultItem {
+              content: Buffer::from(entry.content.clone()),
+              warnings: entry.warnings.clone(),
+              diagnostics: entry.diagnostics.clone(),
+              from_cache: true,
+              class_map: entry.class_map.clone(),
+            },
+          );
+          continue;
         }
       }
 
-      // 添加源文件
-      for (name, data) in &cfg.src {
-        let content = String::from_utf8_lossy(data.as_ref()).into_owned();
-        let str = content.as_str();
-        let warn = ssr.add_source(name.as_str(), str);
-        let mut arr: Vec<String> = Vec::new();
-        for w in warn {
-          arr.push(String::from(w.message.as_str()));
-        }
-        result.files.insert(
-          name.clone(),
-          CompileResultItem {
-            content: Buffer::from(Vec::new()),
-            warnings: arr,
-          },
-        );
+      // Miss: re-parse the file into the persistent resource.
+      changed = true;
+      if !cfg.tag_name_prefix.is_empty() {
+        self
+          .ssr
+          .add_tag_name_prefix(name.as_str(), &cfg.tag_name_prefix);
+      }
+      let content = String::from_utf8_lossy(data.as_ref()).into_owned();
+      let (source, class_map) = scoped_source(name.as_str(), &content, &cfg.scope_mode);
+      let warn = self.ssr.add_source(name.as_str(), source.as_str());
+      let mut arr: Vec<String> = Vec::new();
+      let mut diagnostics: Vec<CompileDiagnostic> = Vec::new();
+      for w in warn {
+        let message = String::from(w.message.as_str());
+        diagnostics.push(diagnostic_from_message(message.clone()));
+        arr.push(message);
       }
+      let (buf, mut unknown) = serialize_output(&self.ssr, name.as_str(), &output_type);
+      arr.append(&mut unknown);
+      self.cache.insert(
+        name.clone(),
+        CacheEntry {
+          hash,
+          content: buf.to_vec(),
+          warnings: arr.clone(),
+          diagnostics: diagnostics.clone(),
+          class_map: class_map.clone(),
+        },
+      );
+      result.files.insert(
+        name.clone(),
+        CompileResultItem {
+          content: buf,
+          warnings: arr,
+          diagnostics,
+          from_cache: false,
+          class_map,
+        },
+      );
+    }
 
-      // 序列化每个文件
-      for name in cfg.src.keys() {
-        if let Some(bincode) = ssr.serialize_bincode(name.as_str()) {
-          if let Some(file) = result.files.get_mut(name) {
-            file.content = Buffer::from(bincode);
-          }
+    // Drop cache entries for files no longer present so renames recompile.
+    let before = self.cache.len();
+    self.cache.retain(|name, _| cfg.src.contains_key(name));
+    let shrank = self.cache.len() < before;
+
+    // A pure deletion leaves `changed` false yet the removed file still
+    // lingers in `self.ssr` (and therefore in the import index). Rebuild the
+    // resource from the surviving sources so stale rules never leak through.
+    if shrank {
+      self.ssr = StyleSheetResource::new();
+      if !cfg.tag_name_prefix.is_empty() {
+        for name in cfg.src.keys() {
+          self
+            .ssr
+            .add_tag_name_prefix(name.as_str(), &cfg.tag_name_prefix);
         }
       }
+      for (name, data) in &cfg.src {
+        let content = String::from_utf8_lossy(data.as_ref()).into_owned();
+        let (source, _) = scoped_source(name.as_str(), &content, &cfg.scope_mode);
+        self.ssr.add_source(name.as_str(), source.as_str());
+      }
+      changed = true;
+    }
 
-      // 生成导入索引
-      let index = ssr.generate_import_indexes();
-      result.import_index = Buffer::from(index.serialize_bincode());
+    if changed {
+      let index = self.ssr.generate_import_indexes();
+      self.import_index = index.serialize_bincode();
     }
+    result.import_index = Buffer::from(self.import_index.clone());
+    result
+  }
+}
 
-    Ok(result)
+#[napi]
+pub fn compile_sync(cfg: CompileOption) -> CompileResult {
+  let output_type = cfg.output_type;
+  if cfg.flatten_imports.unwrap_or(false) {
+    return compile_flattened(&cfg.src, &cfg.tag_name_prefix, &output_type, &cfg.scope_mode);
+  }
+  compile_sources(
+    &cfg.src,
+    &cfg.tag_name_prefix,
+    &output_type,
+    &cfg.scope_mode,
+    cfg.concurrency,
+  )
+}
+
+#[napi]
+pub async fn compile(cfg: CompileOption) -> napi::Result<CompileResult> {
+  // 将耗时操作放在线程池中执行
+  napi::tokio::spawn(async move {
+    let output_type = cfg.output_type;
+    if cfg.flatten_imports.unwrap_or(false) {
+      return Ok(compile_flattened(
+        &cfg.src,
+        &cfg.tag_name_prefix,
+        &output_type,
+        &cfg.scope_mode,
+      ));
+    }
+    Ok(compile_sources(
+      &cfg.src,
+      &cfg.tag_name_prefix,
+      &output_type,
+      &cfg.scope_mode,
+      cfg.concurrency,
+    ))
   })
   .await
   .map_err(|e| {
@@ -146,24 +753,28 @@ pub async fn compile_single(cfg: CompileSingleOption) -> napi::Result<CompileRes
     let mut result = CompileResultItem {
       content: Buffer::from(Vec::new()),
       warnings: Vec::new(),
+      diagnostics: Vec::new(),
+      from_cache: false,
+      class_map: HashMap::new(),
     };
-    if output_type == "bincode" {
-      let mut ssr = StyleSheetResource::new();
-      if !cfg.tag_name_prefix.is_empty() {
-        ssr.add_tag_name_prefix(&cfg.file_name, &cfg.tag_name_prefix);
-      }
-      let content = String::from_utf8_lossy(&cfg.file_content).into_owned();
-      let str = content.as_str();
-      let warn = ssr.add_source(&cfg.file_name, str);
-      for w in warn {
-        result.warnings.push(String::from(w.message.as_str()));
-      }
-
-      // 序列化文件
-      if let Some(bincode) = ssr.serialize_bincode(&cfg.file_name) {
-        result.content = Buffer::from(bincode);
-      }
+    let mut ssr = StyleSheetResource::new();
+    if !cfg.tag_name_prefix.is_empty() {
+      ssr.add_tag_name_prefix(&cfg.file_name, &cfg.tag_name_prefix);
+    }
+    let content = String::from_utf8_lossy(&cfg.file_content).into_owned();
+    let (source, class_map) = scoped_source(&cfg.file_name, &content, &cfg.scope_mode);
+    result.class_map = class_map;
+    let warn = ssr.add_source(&cfg.file_name, source.as_str());
+    for w in warn {
+      let message = String::from(w.message.as_str());
+      result.diagnostics.push(diagnostic_from_message(message.clone()));
+      result.warnings.push(message);
     }
+
+    // 序列化文件
+    let (content, mut warnings) = serialize_output(&ssr, &cfg.file_name, &output_type);
+    result.content = content;
+    result.warnings.append(&mut warnings);
     Ok(result)
   })
   .await
@@ -182,23 +793,101 @@ pub fn compile_single_sync(cfg: CompileSingleOption) -> CompileResultItem {
   let mut result = CompileResultItem {
     content: Buffer::from(Vec::new()),
     warnings: Vec::new(),
+    diagnostics: Vec::new(),
+    from_cache: false,
+    class_map: HashMap::new(),
   };
-  if output_type == "bincode" {
-    let mut ssr = StyleSheetResource::new();
-    if !cfg.tag_name_prefix.is_empty() {
-      ssr.add_tag_name_prefix(&cfg.file_name, &cfg.tag_name_prefix);
-    }
-    let content = String::from_utf8_lossy(&cfg.file_content).into_owned();
-    let str = content.as_str();
-    let warn = ssr.add_source(&cfg.file_name, str);
-    for w in warn {
-      result.warnings.push(String::from(w.message.as_str()));
-    }
-
-    // 序列化文件
-    if let Some(bincode) = ssr.serialize_bincode(&cfg.file_name) {
-      result.content = Buffer::from(bincode);
-    }
+  let mut ssr = StyleSheetResource::new();
+  if !cfg.tag_name_prefix.is_empty() {
+    ssr.add_tag_name_prefix(&cfg.file_name, &cfg.tag_name_prefix);
   }
+  let content = String::from_utf8_lossy(&cfg.file_content).into_owned();
+  let (source, class_map) = scoped_source(&cfg.file_name, &content, &cfg.scope_mode);
+  result.class_map = class_map;
+  let warn = ssr.add_source(&cfg.file_name, source.as_str());
+  for w in warn {
+    let message = String::from(w.message.as_str());
+    result.diagnostics.push(diagnostic_from_message(message.clone()));
+    result.warnings.push(message);
+  }
+
+  // 序列化文件
+  let (content, mut warnings) = serialize_output(&ssr, &cfg.file_name, &output_type);
+  result.content = content;
+  result.warnings.append(&mut warnings);
   result
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parser_warnings_are_warning_severity_with_preserved_message() {
+    let d = diagnostic_from_message(String::from("vendor prefix is invalid here"));
+    assert_eq!(d.severity, "warning");
+    assert_eq!(d.message, "vendor prefix is invalid here");
+    // No misclassification by substring; no invented source position.
+    assert_eq!((d.line, d.column, d.length), (0, 0, 0));
+  }
+
+  #[test]
+  fn content_hash_is_stable_and_content_sensitive() {
+    assert_eq!(content_hash(b".a{}"), content_hash(b".a{}"));
+    assert_ne!(content_hash(b".a{}"), content_hash(b".b{}"));
+  }
+
+  #[test]
+  fn imports_ignore_comments_and_strings() {
+    let css = "/* @import \"x.css\"; */ @import \"real.css\"; .a{content:\"@import y\"}";
+    assert_eq!(extract_imports(css), vec![String::from("real.css")]);
+    let stripped = strip_imports(css);
+    assert!(!stripped.contains("real.css"));
+    assert!(stripped.contains("@import \"x.css\"")); // inside comment, untouched
+    assert!(stripped.contains("content:\"@import y\""));
+  }
+
+  #[test]
+  fn order_imports_detects_cycles() {
+    let mut imports: HashMap<String, Vec<String>> = HashMap::new();
+    imports.insert("a".into(), vec!["b".into()]);
+    imports.insert("b".into(), vec!["a".into()]);
+    let mut order = Vec::new();
+    let mut state = HashMap::new();
+    let mut cycle = false;
+    order_imports("a", &imports, &mut order, &mut state, &mut cycle);
+    assert!(cycle);
+  }
+
+  #[test]
+  fn resolve_import_matches_exact_then_basename() {
+    let mut keys: HashMap<String, String> = HashMap::new();
+    keys.insert("foo.css".into(), String::new());
+    assert_eq!(resolve_import("foo.css", &keys), Some("foo.css"));
+    assert_eq!(resolve_import("./foo.css", &keys), Some("foo.css"));
+    assert_eq!(resolve_import("missing.css", &keys), None);
+  }
+
+  #[test]
+  fn hashed_scope_is_deterministic_and_maps_classes() {
+    let css = ".box { color: red } .box .item { width: 50% } a { content: \".x\" }";
+    let (out_a, map_a) = scoped_source("c.css", css, &Some(String::from("hashed")));
+    let (out_b, map_b) = scoped_source("c.css", css, &Some(String::from("hashed")));
+    assert_eq!(out_a, out_b);
+    assert_eq!(map_a, map_b);
+    let scoped = map_a.get("box").expect("box should be scoped");
+    assert!(scoped.starts_with("box__"));
+    assert!(out_a.contains(&format!(".{}", scoped)));
+    // The string literal `.x` must not be rewritten.
+    assert!(out_a.contains("\".x\""));
+    assert!(!map_a.contains_key("x"));
+  }
+
+  #[test]
+  fn scope_is_noop_without_hashed_mode() {
+    let css = ".box {}";
+    let (out, map) = scoped_source("c.css", css, &None);
+    assert_eq!(out, css);
+    assert!(map.is_empty());
+  }
+}